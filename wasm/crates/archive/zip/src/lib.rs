@@ -4,6 +4,15 @@ use serde::{Serialize, Deserialize};
 use crc32fast::Hasher as Crc32;
 use flate2::{Compression, write::DeflateEncoder, read::DeflateDecoder};
 use std::io::{Read, Write};
+use zip_format::{
+    METHOD_AES, SENTINEL32,
+    le_u16, le_u32, sentinel32_or, zip64_central_extra, zip64_local_extra,
+    read_zip64_central_extra, locate_central_directory, write_eocd,
+    encrypt_winzip_aes, decrypt_winzip_aes, ae_extra_field, read_ae_extra_field,
+    header_crc32, version_needed, to_js,
+};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
 
 #[derive(Serialize, Deserialize)]
 struct FileEntry {
@@ -18,27 +27,53 @@ struct PackOptions {
     /// Optional compression level 0..=9 (default 6 if compressInside)
     #[serde(default)]
     level: Option<u32>,
+    /// Optional password; when set, every entry is encrypted with WinZip AE-2
+    /// (PBKDF2-HMAC-SHA1 / AES-CTR / HMAC-SHA1).
+    #[serde(default)]
+    password: Option<String>,
+    /// AES key size in bits when `password` is set: 128, 192, or 256 (default 256).
+    #[serde(default)]
+    aes_bits: Option<u16>,
+    /// A complete PNG file to prepend so the output is simultaneously a valid PNG and
+    /// a valid ZIP — the archive follows the PNG's `IEND` chunk, after zero padding to `png_align`.
+    #[serde(default)]
+    png_cover: Option<Vec<u8>>,
+    /// Byte alignment to pad the embedded ZIP region to, when `png_cover` is set (default 4).
+    #[serde(default)]
+    png_align: Option<u32>,
 }
 
 #[wasm_bindgen]
 pub fn pack(files: Array, options: JsValue) -> Result<Uint8Array, JsValue> {
     let opts: PackOptions = serde_wasm_bindgen::from_value(options)
-        .unwrap_or(PackOptions { compress_inside: false, level: None });
+        .unwrap_or(PackOptions { compress_inside: false, level: None, password: None, aes_bits: None, png_cover: None, png_align: None });
 
     let level = opts.level.unwrap_or(6).min(9) as u32;
     let use_deflate = opts.compress_inside;
+    let aes_bits = opts.aes_bits.unwrap_or(256);
 
-    // Where we accumulate the whole .zip file.
+    // Where we accumulate the whole output file. When `png_cover` is set this starts
+    // with the PNG bytes, so every offset computed below (via `out.len()`) is already
+    // the true absolute byte position in the final polyglot file, not zero-based.
     let mut out: Vec<u8> = Vec::new();
+    if let Some(ref cover) = opts.png_cover {
+        if cover.len() < 8 || cover[..8] != PNG_SIGNATURE[..] {
+            return Err(JsValue::from_str("png_cover is not a valid PNG (bad signature)"));
+        }
+        out.extend_from_slice(cover);
+        pad_start(&mut out, opts.png_align.unwrap_or(4) as usize);
+    }
 
     // We need to remember central dir records and the local header offsets.
     struct CdRec {
         name: String,
         crc32: u32,
-        comp_size: u32,
-        uncomp_size: u32,
+        comp_size: u64,
+        uncomp_size: u64,
         method: u16,
-        rel_offset: u32,
+        flags: u16,
+        aes_extra: Vec<u8>,
+        rel_offset: u64,
     }
     let mut central: Vec<CdRec> = Vec::new();
 
@@ -52,7 +87,7 @@ pub fn pack(files: Array, options: JsValue) -> Result<Uint8Array, JsValue> {
         hasher.update(&file.data);
         let crc = hasher.finalize();
 
-        let (method, payload): (u16, Vec<u8>) = if use_deflate {
+        let (real_method, deflated): (u16, Vec<u8>) = if use_deflate {
             let mut enc = DeflateEncoder::new(Vec::new(), Compression::new(level));
             enc.write_all(&file.data).map_err(to_js)?;
             (8, enc.finish().map_err(to_js)?)
@@ -60,148 +95,199 @@ pub fn pack(files: Array, options: JsValue) -> Result<Uint8Array, JsValue> {
             (0, file.data.clone())
         };
 
+        let (method, flags, aes_extra, payload) = match opts.password {
+            Some(ref pw) => {
+                let encrypted = encrypt_winzip_aes(&deflated, pw, aes_bits)?;
+                (METHOD_AES, 0x0001u16, ae_extra_field(aes_bits, real_method)?, encrypted)
+            }
+            None => (real_method, 0u16, Vec::new(), deflated),
+        };
+
         let fname = file.name.as_bytes();
         if fname.len() > u16::MAX as usize {
             return Err(JsValue::from_str("Filename too long for ZIP"));
         }
 
+        let uncomp_size = file.data.len() as u64;
+        let comp_size = payload.len() as u64;
+
         // Record where this local file header starts (relative offset for central dir)
-        let local_header_offset = out.len() as u32;
+        let local_header_offset = out.len() as u64;
+
+        let local_extra = zip64_local_extra(uncomp_size, comp_size);
+        let mut extra = Vec::new();
+        extra.extend_from_slice(&local_extra);
+        extra.extend_from_slice(&aes_extra);
 
         // ---- Local file header ----
         // signature
         out.extend_from_slice(b"PK\x03\x04");
-        // version needed to extract (2.0)
-        out.extend_from_slice(&u16::to_le_bytes(20));
+        // version needed to extract (2.0, or 4.5/5.1 if ZIP64/AES)
+        out.extend_from_slice(&u16::to_le_bytes(version_needed(method, !local_extra.is_empty())));
         // general purpose bit flag
-        out.extend_from_slice(&u16::to_le_bytes(0));
+        out.extend_from_slice(&u16::to_le_bytes(flags));
         // compression method
         out.extend_from_slice(&u16::to_le_bytes(method));
         // file mod time/date (0 for now)
         out.extend_from_slice(&u16::to_le_bytes(0)); // time
         out.extend_from_slice(&u16::to_le_bytes(0)); // date
-        // crc32
-        out.extend_from_slice(&u32::to_le_bytes(crc));
+        // crc32 (0 for AE-2: integrity comes from the HMAC instead)
+        out.extend_from_slice(&u32::to_le_bytes(header_crc32(method, crc)));
         // compressed size
-        out.extend_from_slice(&u32::to_le_bytes(payload.len() as u32));
+        out.extend_from_slice(&sentinel32_or(comp_size, comp_size >= SENTINEL32 as u64));
         // uncompressed size
-        out.extend_from_slice(&u32::to_le_bytes(file.data.len() as u32));
+        out.extend_from_slice(&sentinel32_or(uncomp_size, uncomp_size >= SENTINEL32 as u64));
         // filename length
         out.extend_from_slice(&u16::to_le_bytes(fname.len() as u16));
         // extra length
-        out.extend_from_slice(&u16::to_le_bytes(0));
+        out.extend_from_slice(&u16::to_le_bytes(extra.len() as u16));
 
         // filename
         out.extend_from_slice(fname);
-        // extra (none)
+        // extra (zip64 / AE-x)
+        out.extend_from_slice(&extra);
         // file data
         out.extend_from_slice(&payload);
 
         central.push(CdRec {
             name: file.name,
             crc32: crc,
-            comp_size: payload.len() as u32,
-            uncomp_size: file.data.len() as u32,
+            comp_size,
+            uncomp_size,
             method,
+            flags,
+            aes_extra,
             rel_offset: local_header_offset,
         });
     }
 
     // Central directory start
-    let cd_start = out.len();
+    let cd_start = out.len() as u64;
 
     // ---- Central directory entries ----
     for rec in &central {
         let fname = rec.name.as_bytes();
+        let central_extra_zip64 = zip64_central_extra(rec.uncomp_size, rec.comp_size, rec.rel_offset);
+        let mut extra = Vec::new();
+        extra.extend_from_slice(&central_extra_zip64);
+        extra.extend_from_slice(&rec.aes_extra);
 
         out.extend_from_slice(b"PK\x01\x02");             // central dir sig
-        out.extend_from_slice(&u16::to_le_bytes(20));      // version made by
-        out.extend_from_slice(&u16::to_le_bytes(20));      // version needed to extract
-        out.extend_from_slice(&u16::to_le_bytes(0));       // flags
+        out.extend_from_slice(&u16::to_le_bytes(45));      // version made by (zip64-aware)
+        out.extend_from_slice(&u16::to_le_bytes(version_needed(rec.method, !central_extra_zip64.is_empty()))); // version needed
+        out.extend_from_slice(&u16::to_le_bytes(rec.flags)); // flags
         out.extend_from_slice(&u16::to_le_bytes(rec.method));
         out.extend_from_slice(&u16::to_le_bytes(0));       // time
         out.extend_from_slice(&u16::to_le_bytes(0));       // date
-        out.extend_from_slice(&u32::to_le_bytes(rec.crc32));
-        out.extend_from_slice(&u32::to_le_bytes(rec.comp_size));
-        out.extend_from_slice(&u32::to_le_bytes(rec.uncomp_size));
+        out.extend_from_slice(&u32::to_le_bytes(header_crc32(rec.method, rec.crc32)));
+        out.extend_from_slice(&sentinel32_or(rec.comp_size, rec.comp_size >= SENTINEL32 as u64));
+        out.extend_from_slice(&sentinel32_or(rec.uncomp_size, rec.uncomp_size >= SENTINEL32 as u64));
         out.extend_from_slice(&u16::to_le_bytes(fname.len() as u16)); // fname len
-        out.extend_from_slice(&u16::to_le_bytes(0));       // extra len
+        out.extend_from_slice(&u16::to_le_bytes(extra.len() as u16)); // extra len
         out.extend_from_slice(&u16::to_le_bytes(0));       // comment len
         out.extend_from_slice(&u16::to_le_bytes(0));       // disk start
         out.extend_from_slice(&u16::to_le_bytes(0));       // int attrs
         out.extend_from_slice(&u32::to_le_bytes(0));       // ext attrs
-        out.extend_from_slice(&u32::to_le_bytes(rec.rel_offset)); // rel offset local header
+        out.extend_from_slice(&sentinel32_or(rec.rel_offset, rec.rel_offset >= SENTINEL32 as u64)); // rel offset local header
         out.extend_from_slice(fname);
+        out.extend_from_slice(&extra);
     }
 
-    let cd_size = out.len() - cd_start;
+    let cd_size = (out.len() as u64) - cd_start;
 
-    // ---- End of central directory ----
-    out.extend_from_slice(b"PK\x05\x06");
-    out.extend_from_slice(&u16::to_le_bytes(0)); // disk no
-    out.extend_from_slice(&u16::to_le_bytes(0)); // disk where cd starts
-    out.extend_from_slice(&u16::to_le_bytes(central.len() as u16)); // entries this disk
-    out.extend_from_slice(&u16::to_le_bytes(central.len() as u16)); // total entries
-    out.extend_from_slice(&u32::to_le_bytes(cd_size as u32));       // cd size
-    out.extend_from_slice(&u32::to_le_bytes(cd_start as u32));      // cd offset
-    out.extend_from_slice(&u16::to_le_bytes(0));                    // comment len
+    write_eocd(&mut out, central.len() as u64, cd_size, cd_start);
+
+    if opts.png_cover.is_some() {
+        pad_end(&mut out, opts.png_align.unwrap_or(4) as usize);
+    }
 
     Ok(Uint8Array::from(out.as_slice()))
 }
 
 #[wasm_bindgen]
-pub fn unpack(archive: Uint8Array) -> Result<Array, JsValue> {
+pub fn unpack(archive: Uint8Array, password: Option<String>) -> Result<Array, JsValue> {
     let data: Vec<u8> = archive.to_vec();
-    let mut pos: usize = 0;
     let files = Array::new();
 
-    while pos + 30 <= data.len() {
-        // Look for a local header
-        if &data[pos..pos + 4] != b"PK\x03\x04" {
-            pos += 1;
-            continue;
-        }
+    // Walk the central directory (located via the EOCD) rather than scanning forward
+    // for "PK\x03\x04" from byte 0: a forward scan can be fooled by caller-supplied
+    // bytes placed ahead of the real archive (e.g. `png_cover`) that happen to contain
+    // that 4-byte sequence. The central directory also gives us the true sizes/CRC for
+    // every entry directly, including ones written with a data-descriptor (flag bit 3).
+    let (cd_start, cd_size) = locate_central_directory(&data)?;
+    let cd_start = cd_start as usize;
+    if cd_start > data.len() {
+        return Err(JsValue::from_str("Corrupt ZIP: central directory offset exceeds buffer"));
+    }
+    let cd_end = cd_start.saturating_add(cd_size as usize).min(data.len());
 
-        // Local file header fields
-        let _ver_needed = le_u16(&data, pos + 4);
-        let flags       = le_u16(&data, pos + 6);
-        let method      = le_u16(&data, pos + 8);
-        // let time     = le_u16(&data, pos + 10);
-        // let date     = le_u16(&data, pos + 12);
-        let _crc32      = le_u32(&data, pos + 14);
-        let comp_size   = le_u32(&data, pos + 18) as usize;
-        let uncomp_size = le_u32(&data, pos + 22) as usize;
-        let fname_len   = le_u16(&data, pos + 26) as usize;
-        let extra_len   = le_u16(&data, pos + 28) as usize;
-
-        // Data descriptors (flag bit 3) are NOT supported here
-        if (flags & 0x0008) != 0 {
-            return Err(JsValue::from_str("Unsupported ZIP: data descriptor (flag bit 3) set"));
+    let mut pos = cd_start;
+    while pos + 4 <= cd_end {
+        if &data[pos..pos + 4] != b"PK\x01\x02" {
+            return Err(JsValue::from_str("Corrupt ZIP: malformed central directory entry"));
         }
-
-        let header_end = pos + 30;
-        if header_end + fname_len + extra_len > data.len() {
-            return Err(JsValue::from_str("Corrupt ZIP: header exceeds buffer"));
+        if pos + 46 > cd_end {
+            return Err(JsValue::from_str("Corrupt ZIP: truncated central directory entry"));
         }
 
-        let name_start = header_end;
-        let name_end   = name_start + fname_len;
-        let filename   = match std::str::from_utf8(&data[name_start..name_end]) {
-            Ok(s) => s.to_string(),
-            Err(_) => return Err(JsValue::from_str("Invalid UTF-8 in filename")),
-        };
-
-        let file_data_start = name_end + extra_len;
-        let file_data_end   = file_data_start.saturating_add(comp_size);
+        let method          = le_u16(&data, pos + 10);
+        let header_crc      = le_u32(&data, pos + 16);
+        let comp_size_raw   = le_u32(&data, pos + 20);
+        let uncomp_size_raw = le_u32(&data, pos + 24);
+        let fname_len       = le_u16(&data, pos + 28) as usize;
+        let extra_len       = le_u16(&data, pos + 30) as usize;
+        let comment_len     = le_u16(&data, pos + 32) as usize;
+        let rel_offset_raw  = le_u32(&data, pos + 42);
+
+        let name_start = pos + 46;
+        let name_end = name_start + fname_len;
+        let extra_start = name_end;
+        let extra_end = extra_start + extra_len;
+        if extra_end > cd_end {
+            return Err(JsValue::from_str("Corrupt ZIP: central directory entry exceeds its region"));
+        }
+        let filename = std::str::from_utf8(&data[name_start..name_end])
+            .map_err(|_| JsValue::from_str("Invalid UTF-8 in filename"))?
+            .to_string();
+        let extra = &data[extra_start..extra_end];
+
+        // Each of uncompressed/compressed size and the local-header offset is only
+        // promoted to the ZIP64 extra field when its own classic 32-bit field
+        // overflowed, independently of the others.
+        let (uncomp_size, comp_size, rel_offset) =
+            read_zip64_central_extra(extra, uncomp_size_raw, comp_size_raw, rel_offset_raw)?;
+        let comp_size = comp_size as usize;
+        let uncomp_size = uncomp_size as usize;
+        let rel_offset = rel_offset as usize;
+
+        // The central directory gives us everything we need; the local header is only
+        // consulted to skip past its (name, extra) fields to find where the payload starts.
+        if rel_offset + 30 > data.len() || &data[rel_offset..rel_offset + 4] != b"PK\x03\x04" {
+            return Err(JsValue::from_str("Corrupt ZIP: local header offset in central directory is invalid"));
+        }
+        let local_fname_len = le_u16(&data, rel_offset + 26) as usize;
+        let local_extra_len = le_u16(&data, rel_offset + 28) as usize;
+        let file_data_start = rel_offset + 30 + local_fname_len + local_extra_len;
+        let file_data_end = file_data_start.saturating_add(comp_size);
         if file_data_end > data.len() {
             return Err(JsValue::from_str("Corrupt ZIP: file data exceeds buffer"));
         }
 
         let raw = &data[file_data_start..file_data_end];
-        let file_bytes = match method {
-            0 => raw.to_vec(), // stored
+        // `expected_crc32` is `None` only for AE-2 entries, which store no CRC in the
+        // header and are instead authenticated by the trailing HMAC.
+        let (real_method, plain, expected_crc32) = if method == METHOD_AES {
+            let pw = password.clone().ok_or_else(|| JsValue::from_str("This archive is password-protected"))?;
+            let (real_method, aes_bits) = read_ae_extra_field(extra)?;
+            (real_method, decrypt_winzip_aes(raw, &pw, aes_bits)?, None)
+        } else {
+            (method, raw.to_vec(), Some(header_crc))
+        };
+
+        let file_bytes = match real_method {
+            0 => plain, // stored
             8 => {
-                let mut dec = DeflateDecoder::new(raw);
+                let mut dec = DeflateDecoder::new(&plain[..]);
                 let mut buf = Vec::with_capacity(uncomp_size);
                 dec.read_to_end(&mut buf).map_err(to_js)?;
                 buf
@@ -209,28 +295,41 @@ pub fn unpack(archive: Uint8Array) -> Result<Array, JsValue> {
             _ => return Err(JsValue::from_str("Unsupported compression method")),
         };
 
+        if let Some(expected) = expected_crc32 {
+            let mut hasher = Crc32::new();
+            hasher.update(&file_bytes);
+            if hasher.finalize() != expected {
+                return Err(JsValue::from_str("Corrupt ZIP: CRC-32 mismatch"));
+            }
+        }
+
         let entry = FileEntry { name: filename, data: file_bytes };
         let js = serde_wasm_bindgen::to_value(&entry).map_err(to_js)?;
         files.push(&js);
 
-        // Next local header (after file payload)
-        pos = file_data_end;
+        // Next central directory entry
+        pos = extra_end + comment_len;
     }
 
     Ok(files)
 }
 
 // ---- helpers ----
-fn to_js<E: std::fmt::Display>(e: E) -> JsValue {
-    JsValue::from_str(&format!("{e}"))
-}
 
-#[inline]
-fn le_u16(buf: &[u8], i: usize) -> u16 {
-    u16::from_le_bytes([buf[i], buf[i + 1]])
+/// Pad `buf` with zero bytes, before any further writes, until its length is a
+/// multiple of `align` — used to align the embedded ZIP region's start within a PNG cover.
+fn pad_start(buf: &mut Vec<u8>, align: usize) {
+    if align <= 1 {
+        return;
+    }
+    let rem = buf.len() % align;
+    if rem != 0 {
+        buf.extend(std::iter::repeat(0u8).take(align - rem));
+    }
 }
 
-#[inline]
-fn le_u32(buf: &[u8], i: usize) -> u32 {
-    u32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]])
+/// Pad `buf` with trailing zero bytes until its length is a multiple of `align` —
+/// used to align the embedded ZIP region's end within a PNG cover.
+fn pad_end(buf: &mut Vec<u8>, align: usize) {
+    pad_start(buf, align)
 }
@@ -1,6 +1,9 @@
 use wasm_bindgen::prelude::*;
 use js_sys::Uint8Array;
 use brotli::{CompressorReader, Decompressor};
+use brotli::{CompressorWriter, DecompressorWriter};
+use std::io::Write;
+use std::mem;
 
 #[wasm_bindgen]
 pub fn compress(input: Uint8Array, level: Option<u32>) -> Uint8Array {
@@ -17,10 +20,74 @@ pub fn compress(input: Uint8Array, level: Option<u32>) -> Uint8Array {
 #[wasm_bindgen]
 pub fn decompress(input: Uint8Array) -> Uint8Array {
     let input_vec: Vec<u8> = input.to_vec();
-    
+
     let mut decompressed = Vec::new();
     let mut reader = Decompressor::new(&input_vec[..], 4096);
     std::io::copy(&mut reader, &mut decompressed).unwrap();
-    
+
     Uint8Array::from(&decompressed[..])
 }
+
+/// Stateful chunked compressor so a producer can feed fixed-size slices (e.g. from a
+/// `ReadableStream`) without ever materializing the whole payload in memory.
+#[wasm_bindgen]
+pub struct StreamCompressor {
+    writer: Option<CompressorWriter<Vec<u8>>>,
+}
+
+#[wasm_bindgen]
+impl StreamCompressor {
+    #[wasm_bindgen(constructor)]
+    pub fn new(level: Option<u32>) -> StreamCompressor {
+        let quality = level.unwrap_or(6).min(11) as u32;
+        StreamCompressor { writer: Some(CompressorWriter::new(Vec::new(), 4096, quality, 22)) }
+    }
+
+    /// Feed the next input chunk, returning whatever compressed output is ready so far.
+    pub fn push(&mut self, chunk: Uint8Array) -> Result<Uint8Array, JsValue> {
+        let writer = self.writer.as_mut().ok_or_else(|| JsValue::from_str("StreamCompressor already finished"))?;
+        writer.write_all(&chunk.to_vec()).map_err(to_js)?;
+        let produced = mem::take(writer.get_mut());
+        Ok(Uint8Array::from(&produced[..]))
+    }
+
+    /// Flush the tail of the stream; the compressor can't be used again afterwards.
+    pub fn finish(&mut self) -> Result<Uint8Array, JsValue> {
+        let mut writer = self.writer.take().ok_or_else(|| JsValue::from_str("StreamCompressor already finished"))?;
+        writer.flush().map_err(to_js)?;
+        Ok(Uint8Array::from(&writer.into_inner()[..]))
+    }
+}
+
+/// Stateful chunked decompressor; tolerates an input chunk boundary landing mid-token
+/// by buffering until enough bytes have arrived to make progress.
+#[wasm_bindgen]
+pub struct StreamDecompressor {
+    writer: Option<DecompressorWriter<Vec<u8>>>,
+}
+
+#[wasm_bindgen]
+impl StreamDecompressor {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> StreamDecompressor {
+        StreamDecompressor { writer: Some(DecompressorWriter::new(Vec::new(), 4096)) }
+    }
+
+    /// Feed the next compressed chunk, returning whatever decompressed output is ready so far.
+    pub fn push(&mut self, chunk: Uint8Array) -> Result<Uint8Array, JsValue> {
+        let writer = self.writer.as_mut().ok_or_else(|| JsValue::from_str("StreamDecompressor already finished"))?;
+        writer.write_all(&chunk.to_vec()).map_err(to_js)?;
+        let produced = mem::take(writer.get_mut());
+        Ok(Uint8Array::from(&produced[..]))
+    }
+
+    /// Flush any remaining buffered output; the decompressor can't be used again afterwards.
+    pub fn finish(&mut self) -> Result<Uint8Array, JsValue> {
+        let writer = self.writer.take().ok_or_else(|| JsValue::from_str("StreamDecompressor already finished"))?;
+        Ok(Uint8Array::from(&writer.into_inner()[..]))
+    }
+}
+
+fn to_js<E: std::fmt::Display>(e: E) -> JsValue {
+    JsValue::from_str(&format!("{e}"))
+}
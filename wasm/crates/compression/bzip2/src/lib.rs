@@ -0,0 +1,31 @@
+use wasm_bindgen::prelude::*;
+use js_sys::Uint8Array;
+use std::io::Read;
+
+// Pure-Rust bzip2 decoder (via `bzip2-rs`), not the `bzip2`/`bzip2-sys` C bindings: this
+// crate compiles to `wasm32-unknown-unknown` with no C toolchain, same as flate2/brotli.
+// There's no mature pure-Rust bzip2 *encoder* yet, so compression is intentionally
+// unsupported here rather than pulling the C-backed encoder back in for one direction.
+
+#[wasm_bindgen]
+pub fn compress(_input: Uint8Array, _level: Option<u32>) -> Result<Uint8Array, JsValue> {
+    Err(JsValue::from_str(
+        "bzip2 compression is unsupported in this wasm build (no pure-Rust encoder available); \
+         only decompression is supported",
+    ))
+}
+
+#[wasm_bindgen]
+pub fn decompress(input: Uint8Array) -> Result<Uint8Array, JsValue> {
+    let input_vec: Vec<u8> = input.to_vec();
+
+    let mut decoder = bzip2_rs::DecoderReader::new(&input_vec[..]);
+    let mut decompressed = Vec::new();
+    decoder.read_to_end(&mut decompressed).map_err(to_js)?;
+
+    Ok(Uint8Array::from(&decompressed[..]))
+}
+
+fn to_js<E: std::fmt::Display>(e: E) -> JsValue {
+    JsValue::from_str(&format!("{e}"))
+}
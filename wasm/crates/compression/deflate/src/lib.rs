@@ -0,0 +1,123 @@
+use wasm_bindgen::prelude::*;
+use js_sys::Uint8Array;
+use flate2::{Compression, Decompress, FlushDecompress, Status, write::DeflateEncoder};
+use std::io::Write;
+use std::mem;
+
+#[wasm_bindgen]
+pub fn compress(input: Uint8Array, level: Option<u32>) -> Uint8Array {
+    let input_vec: Vec<u8> = input.to_vec();
+    let lvl = level.unwrap_or(6).min(9);
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::new(lvl));
+    encoder.write_all(&input_vec).unwrap();
+
+    let compressed = encoder.finish().unwrap();
+    Uint8Array::from(&compressed[..])
+}
+
+#[wasm_bindgen]
+pub fn decompress(input: Uint8Array) -> Uint8Array {
+    let input_vec: Vec<u8> = input.to_vec();
+
+    let mut decompress = Decompress::new(false);
+    let mut out = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let offset = decompress.total_in() as usize;
+        if offset >= input_vec.len() {
+            break;
+        }
+        let before_out = decompress.total_out();
+        let status = decompress.decompress(&input_vec[offset..], &mut buf, FlushDecompress::None).unwrap();
+        out.extend_from_slice(&buf[..(decompress.total_out() - before_out) as usize]);
+        if status == Status::StreamEnd {
+            break;
+        }
+    }
+    Uint8Array::from(&out[..])
+}
+
+/// Stateful chunked compressor so a producer can feed fixed-size slices (e.g. from a
+/// `ReadableStream`) without ever materializing the whole payload in memory.
+#[wasm_bindgen]
+pub struct StreamCompressor {
+    encoder: Option<DeflateEncoder<Vec<u8>>>,
+}
+
+#[wasm_bindgen]
+impl StreamCompressor {
+    #[wasm_bindgen(constructor)]
+    pub fn new(level: Option<u32>) -> StreamCompressor {
+        let lvl = level.unwrap_or(6).min(9);
+        StreamCompressor { encoder: Some(DeflateEncoder::new(Vec::new(), Compression::new(lvl))) }
+    }
+
+    /// Feed the next input chunk, returning whatever compressed output is ready so far.
+    pub fn push(&mut self, chunk: Uint8Array) -> Result<Uint8Array, JsValue> {
+        let encoder = self.encoder.as_mut().ok_or_else(|| JsValue::from_str("StreamCompressor already finished"))?;
+        encoder.write_all(&chunk.to_vec()).map_err(to_js)?;
+        let produced = mem::take(encoder.get_mut());
+        Ok(Uint8Array::from(&produced[..]))
+    }
+
+    /// Flush the tail of the stream; the compressor can't be used again afterwards.
+    pub fn finish(&mut self) -> Result<Uint8Array, JsValue> {
+        let encoder = self.encoder.take().ok_or_else(|| JsValue::from_str("StreamCompressor already finished"))?;
+        let tail = encoder.finish().map_err(to_js)?;
+        Ok(Uint8Array::from(&tail[..]))
+    }
+}
+
+/// Stateful chunked decompressor; tolerates an input chunk boundary landing mid-token
+/// by buffering until enough bytes have arrived to make progress.
+#[wasm_bindgen]
+pub struct StreamDecompressor {
+    decompress: Decompress,
+    done: bool,
+}
+
+#[wasm_bindgen]
+impl StreamDecompressor {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> StreamDecompressor {
+        StreamDecompressor { decompress: Decompress::new(false), done: false }
+    }
+
+    /// Feed the next compressed chunk, returning whatever decompressed output is ready so far.
+    pub fn push(&mut self, chunk: Uint8Array) -> Result<Uint8Array, JsValue> {
+        if self.done {
+            return Ok(Uint8Array::new_with_length(0));
+        }
+        let input = chunk.to_vec();
+        let start_total_in = self.decompress.total_in();
+        let mut out = Vec::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            // Keep calling even once this chunk's input is all consumed: the decoder can
+            // still hold decoded output that didn't fit in `buf` on the previous call.
+            let consumed = (self.decompress.total_in() - start_total_in) as usize;
+            let before_out = self.decompress.total_out();
+            let status = self.decompress.decompress(&input[consumed..], &mut buf, FlushDecompress::None).map_err(to_js)?;
+            let produced = (self.decompress.total_out() - before_out) as usize;
+            out.extend_from_slice(&buf[..produced]);
+            if status == Status::StreamEnd {
+                self.done = true;
+                break;
+            }
+            if (self.decompress.total_in() - start_total_in) as usize == consumed && produced == 0 {
+                break; // no progress possible with the bytes on hand; wait for more
+            }
+        }
+        Ok(Uint8Array::from(out.as_slice()))
+    }
+
+    /// No-op: deflate carries no trailer, so all output is already returned by `push`.
+    pub fn finish(&mut self) -> Uint8Array {
+        Uint8Array::new_with_length(0)
+    }
+}
+
+fn to_js<E: std::fmt::Display>(e: E) -> JsValue {
+    JsValue::from_str(&format!("{e}"))
+}
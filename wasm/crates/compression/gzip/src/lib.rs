@@ -1,28 +1,282 @@
 use wasm_bindgen::prelude::*;
 use js_sys::Uint8Array;
-use flate2::write::{GzEncoder, GzDecoder};
-use flate2::Compression;
-use std::io::Write;
+use serde::{Serialize, Deserialize};
+use crc32fast::Hasher as Crc32;
+use flate2::{GzBuilder, Compression, Decompress, FlushDecompress, Status};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::io::{Read, Write};
+use std::mem;
+
+const MAX_HEADER_FIELD_LEN: usize = 65535; // flate2's own limit for filename/comment
+
+#[derive(Serialize, Deserialize)]
+struct GzOptions {
+    #[serde(default)]
+    filename: Option<String>,
+    #[serde(default)]
+    comment: Option<String>,
+    /// Modification time, seconds since the Unix epoch.
+    #[serde(default)]
+    mtime: Option<u32>,
+    /// Operating-system byte per the gzip spec (e.g. 3 = Unix, 255 = unknown).
+    #[serde(default)]
+    os: Option<u8>,
+}
+
+#[derive(Serialize)]
+struct GzDecompressed {
+    data: Vec<u8>,
+    filename: Option<String>,
+    comment: Option<String>,
+    mtime: u32,
+    os: u8,
+}
 
 #[wasm_bindgen]
-pub fn compress(input: Uint8Array, level: Option<u32>) -> Uint8Array {
+pub fn compress(input: Uint8Array, level: Option<u32>, options: JsValue) -> Result<Uint8Array, JsValue> {
     let input_vec: Vec<u8> = input.to_vec();
     let compression_level = Compression::new(level.unwrap_or(6).min(9));
-    
-    let mut encoder = GzEncoder::new(Vec::new(), compression_level);
-    encoder.write_all(&input_vec).unwrap();
-    
-    let compressed = encoder.finish().unwrap();
-    Uint8Array::from(&compressed[..])
+    let opts: GzOptions = serde_wasm_bindgen::from_value(options)
+        .unwrap_or(GzOptions { filename: None, comment: None, mtime: None, os: None });
+
+    let mut builder = GzBuilder::new();
+    if let Some(filename) = opts.filename {
+        if filename.len() > MAX_HEADER_FIELD_LEN {
+            return Err(JsValue::from_str("gzip filename too long"));
+        }
+        builder = builder.filename(filename);
+    }
+    if let Some(comment) = opts.comment {
+        if comment.len() > MAX_HEADER_FIELD_LEN {
+            return Err(JsValue::from_str("gzip comment too long"));
+        }
+        builder = builder.comment(comment);
+    }
+    if let Some(mtime) = opts.mtime {
+        builder = builder.mtime(mtime);
+    }
+    if let Some(os) = opts.os {
+        builder = builder.operating_system(os);
+    }
+
+    let mut encoder = builder.write(Vec::new(), compression_level);
+    encoder.write_all(&input_vec).map_err(to_js)?;
+    let compressed = encoder.finish().map_err(to_js)?;
+    Ok(Uint8Array::from(&compressed[..]))
 }
 
 #[wasm_bindgen]
 pub fn decompress(input: Uint8Array) -> Uint8Array {
     let input_vec: Vec<u8> = input.to_vec();
-    
-    let mut decoder = GzDecoder::new(Vec::new());
-    decoder.write_all(&input_vec).unwrap();
-    
-    let decompressed = decoder.finish().unwrap();
+
+    let mut decoder = GzDecoder::new(&input_vec[..]);
+    let mut decompressed = Vec::new();
+    decoder.read_to_end(&mut decompressed).unwrap();
+
     Uint8Array::from(&decompressed[..])
 }
+
+/// Decompress a gzip stream while also recovering the header metadata (filename,
+/// comment, mtime, OS) that plain `decompress` throws away.
+#[wasm_bindgen]
+pub fn decompress_with_header(input: Uint8Array) -> Result<JsValue, JsValue> {
+    let input_vec: Vec<u8> = input.to_vec();
+
+    let mut decoder = GzDecoder::new(&input_vec[..]);
+    let mut data = Vec::new();
+    decoder.read_to_end(&mut data).map_err(to_js)?;
+
+    let header = decoder.header().ok_or_else(|| JsValue::from_str("Not a gzip stream (missing header)"))?;
+    let result = GzDecompressed {
+        data,
+        filename: header.filename().map(|b| String::from_utf8_lossy(b).into_owned()),
+        comment: header.comment().map(|b| String::from_utf8_lossy(b).into_owned()),
+        mtime: header.mtime(),
+        os: header.operating_system(),
+    };
+
+    serde_wasm_bindgen::to_value(&result).map_err(to_js)
+}
+
+fn to_js<E: std::fmt::Display>(e: E) -> JsValue {
+    JsValue::from_str(&format!("{e}"))
+}
+
+/// Stateful chunked compressor so a producer can feed fixed-size slices (e.g. from a
+/// `ReadableStream`) without ever materializing the whole payload in memory.
+#[wasm_bindgen]
+pub struct StreamCompressor {
+    encoder: Option<GzEncoder<Vec<u8>>>,
+}
+
+#[wasm_bindgen]
+impl StreamCompressor {
+    #[wasm_bindgen(constructor)]
+    pub fn new(level: Option<u32>) -> StreamCompressor {
+        let lvl = Compression::new(level.unwrap_or(6).min(9));
+        StreamCompressor { encoder: Some(GzEncoder::new(Vec::new(), lvl)) }
+    }
+
+    /// Feed the next input chunk, returning whatever compressed output is ready so far.
+    pub fn push(&mut self, chunk: Uint8Array) -> Result<Uint8Array, JsValue> {
+        let encoder = self.encoder.as_mut().ok_or_else(|| JsValue::from_str("StreamCompressor already finished"))?;
+        encoder.write_all(&chunk.to_vec()).map_err(to_js)?;
+        let produced = mem::take(encoder.get_mut());
+        Ok(Uint8Array::from(&produced[..]))
+    }
+
+    /// Flush the tail of the stream (including the gzip trailer); the compressor
+    /// can't be used again afterwards.
+    pub fn finish(&mut self) -> Result<Uint8Array, JsValue> {
+        let encoder = self.encoder.take().ok_or_else(|| JsValue::from_str("StreamCompressor already finished"))?;
+        let tail = encoder.finish().map_err(to_js)?;
+        Ok(Uint8Array::from(&tail[..]))
+    }
+}
+
+/// Stateful chunked decompressor; tolerates an input chunk boundary landing mid-token
+/// (including inside the gzip header) by buffering until enough bytes have arrived.
+#[wasm_bindgen]
+pub struct StreamDecompressor {
+    header_buf: Vec<u8>,
+    header_parsed: bool,
+    decompress: Decompress,
+    done: bool,
+    crc: Crc32,
+    total_out: u64,
+    trailer_buf: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl StreamDecompressor {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> StreamDecompressor {
+        StreamDecompressor {
+            header_buf: Vec::new(),
+            header_parsed: false,
+            decompress: Decompress::new(false),
+            done: false,
+            crc: Crc32::new(),
+            total_out: 0,
+            trailer_buf: Vec::new(),
+        }
+    }
+
+    /// Feed the next compressed chunk, returning whatever decompressed output is ready so far.
+    pub fn push(&mut self, chunk: Uint8Array) -> Result<Uint8Array, JsValue> {
+        if self.done {
+            // Nothing left to inflate; any further bytes are (more of) the trailer.
+            self.trailer_buf.extend_from_slice(&chunk.to_vec());
+            return Ok(Uint8Array::new_with_length(0));
+        }
+
+        let mut pending = chunk.to_vec();
+        if !self.header_parsed {
+            self.header_buf.extend_from_slice(&pending);
+            match gzip_header_len(&self.header_buf)? {
+                Some(header_len) => {
+                    pending = self.header_buf.split_off(header_len);
+                    self.header_buf.clear();
+                    self.header_parsed = true;
+                }
+                None => return Ok(Uint8Array::new_with_length(0)), // still buffering the header
+            }
+        }
+
+        let start_total_in = self.decompress.total_in();
+        let mut out = Vec::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            // Keep calling even once this chunk's input is all consumed: the decoder can
+            // still hold decoded output that didn't fit in `buf` on the previous call.
+            let consumed = (self.decompress.total_in() - start_total_in) as usize;
+            let before_out = self.decompress.total_out();
+            let status = self.decompress.decompress(&pending[consumed..], &mut buf, FlushDecompress::None).map_err(to_js)?;
+            let produced = (self.decompress.total_out() - before_out) as usize;
+            out.extend_from_slice(&buf[..produced]);
+            if status == Status::StreamEnd {
+                self.done = true;
+                let consumed = (self.decompress.total_in() - start_total_in) as usize;
+                self.trailer_buf.extend_from_slice(&pending[consumed..]);
+                break;
+            }
+            if (self.decompress.total_in() - start_total_in) as usize == consumed && produced == 0 {
+                break; // no progress possible with the bytes on hand; wait for more
+            }
+        }
+        self.crc.update(&out);
+        self.total_out += out.len() as u64;
+        Ok(Uint8Array::from(out.as_slice()))
+    }
+
+    /// Verify the mandatory 8-byte gzip trailer (CRC-32 + ISIZE of the decompressed data)
+    /// against what was actually produced, then report success. There's no payload left
+    /// to flush, so a successful result is always empty.
+    pub fn finish(&mut self) -> Result<Uint8Array, JsValue> {
+        if !self.done {
+            return Err(JsValue::from_str("Truncated gzip stream: missing end-of-stream marker"));
+        }
+        if self.trailer_buf.len() < 8 {
+            return Err(JsValue::from_str("Truncated gzip stream: missing CRC-32/size trailer"));
+        }
+        let stored_crc = u32::from_le_bytes(self.trailer_buf[0..4].try_into().unwrap());
+        let stored_isize = u32::from_le_bytes(self.trailer_buf[4..8].try_into().unwrap());
+        if self.crc.clone().finalize() != stored_crc {
+            return Err(JsValue::from_str("Corrupt gzip stream: CRC-32 mismatch"));
+        }
+        if (self.total_out as u32) != stored_isize {
+            return Err(JsValue::from_str("Corrupt gzip stream: size mismatch"));
+        }
+        Ok(Uint8Array::new_with_length(0))
+    }
+}
+
+/// Length of the gzip header at the start of `buf`, or `None` if more bytes are needed
+/// before the (variable-length, due to FEXTRA/FNAME/FCOMMENT/FHCRC) header can be parsed.
+fn gzip_header_len(buf: &[u8]) -> Result<Option<usize>, JsValue> {
+    if buf.len() < 10 {
+        return Ok(None);
+    }
+    if buf[0] != 0x1f || buf[1] != 0x8b {
+        return Err(JsValue::from_str("Not a gzip stream (bad magic bytes)"));
+    }
+    let flags = buf[3];
+    let mut pos = 10usize;
+
+    if flags & 0x04 != 0 {
+        // FEXTRA
+        if buf.len() < pos + 2 {
+            return Ok(None);
+        }
+        let xlen = u16::from_le_bytes([buf[pos], buf[pos + 1]]) as usize;
+        pos += 2;
+        if buf.len() < pos + xlen {
+            return Ok(None);
+        }
+        pos += xlen;
+    }
+    if flags & 0x08 != 0 {
+        // FNAME
+        match buf[pos..].iter().position(|&b| b == 0) {
+            Some(nul) => pos += nul + 1,
+            None => return Ok(None),
+        }
+    }
+    if flags & 0x10 != 0 {
+        // FCOMMENT
+        match buf[pos..].iter().position(|&b| b == 0) {
+            Some(nul) => pos += nul + 1,
+            None => return Ok(None),
+        }
+    }
+    if flags & 0x02 != 0 {
+        // FHCRC
+        if buf.len() < pos + 2 {
+            return Ok(None);
+        }
+        pos += 2;
+    }
+
+    Ok(Some(pos))
+}
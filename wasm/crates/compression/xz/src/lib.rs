@@ -0,0 +1,29 @@
+use wasm_bindgen::prelude::*;
+use js_sys::Uint8Array;
+
+// Pure-Rust xz/lzma (via `lzma-rs`), not the `xz2`/`lzma-sys` C bindings: this crate
+// compiles to `wasm32-unknown-unknown` with no C toolchain, same as flate2/brotli.
+
+#[wasm_bindgen]
+pub fn compress(input: Uint8Array, _level: Option<u32>) -> Result<Uint8Array, JsValue> {
+    let input_vec: Vec<u8> = input.to_vec();
+
+    let mut compressed = Vec::new();
+    lzma_rs::xz_compress(&mut &input_vec[..], &mut compressed).map_err(to_js)?;
+
+    Ok(Uint8Array::from(&compressed[..]))
+}
+
+#[wasm_bindgen]
+pub fn decompress(input: Uint8Array) -> Result<Uint8Array, JsValue> {
+    let input_vec: Vec<u8> = input.to_vec();
+
+    let mut decompressed = Vec::new();
+    lzma_rs::xz_decompress(&mut &input_vec[..], &mut decompressed).map_err(to_js)?;
+
+    Ok(Uint8Array::from(&decompressed[..]))
+}
+
+fn to_js<E: std::fmt::Display>(e: E) -> JsValue {
+    JsValue::from_str(&format!("{e}"))
+}
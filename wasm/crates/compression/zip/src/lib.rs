@@ -3,12 +3,20 @@ use js_sys::Uint8Array;
 use crc32fast::Hasher as Crc32;
 use flate2::{Compression, write::DeflateEncoder, read::DeflateDecoder};
 use std::io::{Read, Write};
+use zip_format::{
+    METHOD_AES, SENTINEL32,
+    le_u16, le_u32, sentinel32_or, zip64_local_extra, zip64_central_extra,
+    has_zip64_extra, read_zip64_local_extra, read_streamed_entry, write_eocd,
+    encrypt_winzip_aes, decrypt_winzip_aes, ae_extra_field, read_ae_extra_field,
+    header_crc32, version_needed, to_js,
+};
 
 /// Compress raw bytes into a *single-file ZIP*.
 /// - `filename` -> name of the single entry inside the zip (default "data.bin")
 /// - `level`    -> 0..=9 (0 -> store/no compression; otherwise deflate)
+/// - `password` -> when set, the entry is encrypted with WinZip AE-2 (PBKDF2-HMAC-SHA1 / AES-256-CTR / HMAC-SHA1)
 #[wasm_bindgen]
-pub fn compress(input: Uint8Array, filename: Option<String>, level: Option<u32>) -> Result<Uint8Array, JsValue> {
+pub fn compress(input: Uint8Array, filename: Option<String>, level: Option<u32>, password: Option<String>) -> Result<Uint8Array, JsValue> {
     let data = input.to_vec();
     let name = filename.unwrap_or_else(|| "data.bin".to_string());
     let lvl = level.unwrap_or(6).min(9);
@@ -19,7 +27,7 @@ pub fn compress(input: Uint8Array, filename: Option<String>, level: Option<u32>)
     let crc = hasher.finalize();
 
     // Choose method: 0=store, 8=deflate
-    let (method, payload): (u16, Vec<u8>) = if lvl == 0 {
+    let (real_method, deflated): (u16, Vec<u8>) = if lvl == 0 {
         (0, data.clone())
     } else {
         let mut enc = DeflateEncoder::new(Vec::new(), Compression::new(lvl));
@@ -32,63 +40,83 @@ pub fn compress(input: Uint8Array, filename: Option<String>, level: Option<u32>)
         return Err(JsValue::from_str("Filename too long"));
     }
 
+    // If a password was given, wrap the (optionally deflated) payload in WinZip AE-2
+    // and store method 99 + the AE-x extra field instead of the real method.
+    let (method, flags, aes_extra, payload) = match password {
+        Some(ref pw) => {
+            let aes_bits = 256u16;
+            let encrypted = encrypt_winzip_aes(&deflated, pw, aes_bits)?;
+            (METHOD_AES, 0x0001u16, ae_extra_field(aes_bits, real_method)?, encrypted)
+        }
+        None => (real_method, 0u16, Vec::new(), deflated),
+    };
+
+    let uncomp_size = data.len() as u64;
+    let comp_size = payload.len() as u64;
+
     let mut out: Vec<u8> = Vec::new();
-    let local_header_offset = out.len() as u32;
+    let local_header_offset = out.len() as u64;
+
+    // ZIP64: classic fields become sentinels and the true sizes move into a 0x0001 extra field.
+    let local_extra = zip64_local_extra(uncomp_size, comp_size);
+    let mut extra = Vec::new();
+    extra.extend_from_slice(&local_extra);
+    extra.extend_from_slice(&aes_extra);
 
     // ---- Local file header (PK\x03\x04) ----
     out.extend_from_slice(b"PK\x03\x04");
-    out.extend_from_slice(&u16::to_le_bytes(20));                 // version needed
-    out.extend_from_slice(&u16::to_le_bytes(0));                  // flags
+    out.extend_from_slice(&u16::to_le_bytes(version_needed(method, !local_extra.is_empty()))); // version needed
+    out.extend_from_slice(&u16::to_le_bytes(flags));              // flags
     out.extend_from_slice(&u16::to_le_bytes(method));             // method
     out.extend_from_slice(&u16::to_le_bytes(0));                  // time
     out.extend_from_slice(&u16::to_le_bytes(0));                  // date
-    out.extend_from_slice(&u32::to_le_bytes(crc));                // crc32
-    out.extend_from_slice(&u32::to_le_bytes(payload.len() as u32));       // comp size
-    out.extend_from_slice(&u32::to_le_bytes(data.len() as u32));          // uncomp size
+    out.extend_from_slice(&u32::to_le_bytes(header_crc32(method, crc))); // crc32 (0 for AE-2)
+    out.extend_from_slice(&sentinel32_or(comp_size, comp_size >= SENTINEL32 as u64));     // comp size
+    out.extend_from_slice(&sentinel32_or(uncomp_size, uncomp_size >= SENTINEL32 as u64)); // uncomp size
     out.extend_from_slice(&u16::to_le_bytes(fname.len() as u16));         // name len
-    out.extend_from_slice(&u16::to_le_bytes(0));                          // extra len
+    out.extend_from_slice(&u16::to_le_bytes(extra.len() as u16));        // extra len
     out.extend_from_slice(fname);                                         // name
+    out.extend_from_slice(&extra);                                        // extra (zip64 / AE-x)
     out.extend_from_slice(&payload);                                      // data
 
     // ---- Central directory (PK\x01\x02) ----
-    let cd_start = out.len();
+    let cd_start = out.len() as u64;
+    let central_extra_zip64 = zip64_central_extra(uncomp_size, comp_size, local_header_offset);
+    let mut central_extra = Vec::new();
+    central_extra.extend_from_slice(&central_extra_zip64);
+    central_extra.extend_from_slice(&aes_extra);
+
     out.extend_from_slice(b"PK\x01\x02");
-    out.extend_from_slice(&u16::to_le_bytes(20));                 // version made by
-    out.extend_from_slice(&u16::to_le_bytes(20));                 // version needed
-    out.extend_from_slice(&u16::to_le_bytes(0));                  // flags
+    out.extend_from_slice(&u16::to_le_bytes(45));                 // version made by (45 = zip64-aware)
+    out.extend_from_slice(&u16::to_le_bytes(version_needed(method, !central_extra_zip64.is_empty()))); // version needed
+    out.extend_from_slice(&u16::to_le_bytes(flags));              // flags
     out.extend_from_slice(&u16::to_le_bytes(method));             // method
     out.extend_from_slice(&u16::to_le_bytes(0));                  // time
     out.extend_from_slice(&u16::to_le_bytes(0));                  // date
-    out.extend_from_slice(&u32::to_le_bytes(crc));                // crc32
-    out.extend_from_slice(&u32::to_le_bytes(payload.len() as u32));// comp size
-    out.extend_from_slice(&u32::to_le_bytes(data.len() as u32));   // uncomp size
+    out.extend_from_slice(&u32::to_le_bytes(header_crc32(method, crc))); // crc32 (0 for AE-2)
+    out.extend_from_slice(&sentinel32_or(comp_size, comp_size >= SENTINEL32 as u64));
+    out.extend_from_slice(&sentinel32_or(uncomp_size, uncomp_size >= SENTINEL32 as u64));
     out.extend_from_slice(&u16::to_le_bytes(fname.len() as u16));  // name len
-    out.extend_from_slice(&u16::to_le_bytes(0));                   // extra len
+    out.extend_from_slice(&u16::to_le_bytes(central_extra.len() as u16));  // extra len
     out.extend_from_slice(&u16::to_le_bytes(0));                   // comment len
     out.extend_from_slice(&u16::to_le_bytes(0));                   // disk start
     out.extend_from_slice(&u16::to_le_bytes(0));                   // int attrs
     out.extend_from_slice(&u32::to_le_bytes(0));                   // ext attrs
-    out.extend_from_slice(&u32::to_le_bytes(local_header_offset)); // rel offset
+    out.extend_from_slice(&sentinel32_or(local_header_offset, local_header_offset >= SENTINEL32 as u64)); // rel offset
     out.extend_from_slice(fname);                                  // name
+    out.extend_from_slice(&central_extra);                         // extra
 
-    let cd_size = out.len() - cd_start;
+    let cd_size = (out.len() as u64) - cd_start;
 
-    // ---- End of central directory (PK\x05\x06) ----
-    out.extend_from_slice(b"PK\x05\x06");
-    out.extend_from_slice(&u16::to_le_bytes(0));                   // disk no
-    out.extend_from_slice(&u16::to_le_bytes(0));                   // disk w/ cd
-    out.extend_from_slice(&u16::to_le_bytes(1));                   // entries this disk
-    out.extend_from_slice(&u16::to_le_bytes(1));                   // total entries
-    out.extend_from_slice(&u32::to_le_bytes(cd_size as u32));      // cd size
-    out.extend_from_slice(&u32::to_le_bytes(cd_start as u32));     // cd offset
-    out.extend_from_slice(&u16::to_le_bytes(0));                   // comment len
+    write_eocd(&mut out, 1, cd_size, cd_start);
 
     Ok(Uint8Array::from(out.as_slice()))
 }
 
 /// Decompress a *single-file ZIP* produced by `compress` back to raw bytes.
+/// `password` is required when the entry was written with WinZip AE-2 encryption (method 99).
 #[wasm_bindgen]
-pub fn decompress(zip: Uint8Array) -> Result<Uint8Array, JsValue> {
+pub fn decompress(zip: Uint8Array, password: Option<String>) -> Result<Uint8Array, JsValue> {
     let buf = zip.to_vec();
     let mut pos = 0usize;
 
@@ -101,39 +129,76 @@ pub fn decompress(zip: Uint8Array) -> Result<Uint8Array, JsValue> {
     }
 
     let flags  = le_u16(&buf, pos + 6);
-    if (flags & 0x0008) != 0 {
-        return Err(JsValue::from_str("Unsupported ZIP: data descriptor set"));
-    }
     let method = le_u16(&buf, pos + 8);
-    let comp_size = le_u32(&buf, pos + 18) as usize;
+    let header_crc = le_u32(&buf, pos + 14);
+    let comp_size_raw = le_u32(&buf, pos + 18);
+    let uncomp_size_raw = le_u32(&buf, pos + 22);
     let fname_len = le_u16(&buf, pos + 26) as usize;
     let extra_len = le_u16(&buf, pos + 28) as usize;
 
     let name_start = pos + 30;
-    let data_start = name_start + fname_len + extra_len;
-    let data_end   = data_start.saturating_add(comp_size);
-    if data_end > buf.len() {
-        return Err(JsValue::from_str("Corrupt ZIP: data beyond buffer"));
+    let extra_start = name_start + fname_len;
+    let extra_end = extra_start + extra_len;
+    if extra_end > buf.len() {
+        return Err(JsValue::from_str("Corrupt ZIP: extra field exceeds buffer"));
     }
-
-    let raw = &buf[data_start..data_end];
-    let out = match method {
-        0 => raw.to_vec(),
-        8 => {
-            let mut dec = DeflateDecoder::new(raw);
-            let mut out = Vec::new();
-            dec.read_to_end(&mut out).map_err(to_js)?;
-            out
+    let extra = &buf[extra_start..extra_end];
+    let data_start = extra_end;
+
+    // Streamed entries (flag bit 3) have zero sizes/CRC in the header; the real
+    // values live in a data descriptor that follows the compressed payload.
+    let streamed = (flags & 0x0008) != 0;
+
+    // `expected_crc32` is `None` only for AE-2 entries, which store no CRC in the
+    // header and are instead authenticated by the trailing HMAC.
+    let (out, expected_crc32): (Vec<u8>, Option<u32>) = if streamed {
+        if method == METHOD_AES {
+            return Err(JsValue::from_str("Unsupported ZIP: AES-encrypted data-descriptor entry"));
         }
-        _ => return Err(JsValue::from_str("Unsupported ZIP method")),
+        let zip64_entry = has_zip64_extra(extra);
+        let (out, crc32, _next_pos) = read_streamed_entry(&buf, data_start, method, zip64_entry)?;
+        (out, Some(crc32))
+    } else {
+        // Each of uncompressed/compressed size is only promoted to the ZIP64 extra
+        // field when its own classic 32-bit field overflowed, independently of the other.
+        let (_uncomp_size, comp_size) = read_zip64_local_extra(extra, uncomp_size_raw, comp_size_raw)?;
+        let comp_size = comp_size as usize;
+
+        let data_end = data_start.saturating_add(comp_size);
+        if data_end > buf.len() {
+            return Err(JsValue::from_str("Corrupt ZIP: data beyond buffer"));
+        }
+
+        let raw = &buf[data_start..data_end];
+        let (real_method, plain, expected_crc32) = if method == METHOD_AES {
+            let pw = password.ok_or_else(|| JsValue::from_str("This entry is password-protected"))?;
+            let (real_method, aes_bits) = read_ae_extra_field(extra)?;
+            (real_method, decrypt_winzip_aes(raw, &pw, aes_bits)?, None)
+        } else {
+            (method, raw.to_vec(), Some(header_crc))
+        };
+
+        let out = match real_method {
+            0 => plain,
+            8 => {
+                let mut dec = DeflateDecoder::new(&plain[..]);
+                let mut out = Vec::new();
+                dec.read_to_end(&mut out).map_err(to_js)?;
+                out
+            }
+            _ => return Err(JsValue::from_str("Unsupported ZIP method")),
+        };
+
+        (out, expected_crc32)
     };
 
+    if let Some(expected) = expected_crc32 {
+        let mut hasher = Crc32::new();
+        hasher.update(&out);
+        if hasher.finalize() != expected {
+            return Err(JsValue::from_str("Corrupt ZIP: CRC-32 mismatch"));
+        }
+    }
+
     Ok(Uint8Array::from(out.as_slice()))
 }
-
-#[inline]
-fn le_u16(buf: &[u8], i: usize) -> u16 { u16::from_le_bytes([buf[i], buf[i+1]]) }
-#[inline]
-fn le_u32(buf: &[u8], i: usize) -> u32 { u32::from_le_bytes([buf[i], buf[i+1], buf[i+2], buf[i+3]]) }
-
-fn to_js<E: std::fmt::Display>(e: E) -> JsValue { JsValue::from_str(&format!("{e}")) }
@@ -0,0 +1,30 @@
+use wasm_bindgen::prelude::*;
+use js_sys::Uint8Array;
+use std::io::Read;
+
+// Pure-Rust zstd (via `ruzstd`), not the `zstd`/`zstd-sys` C bindings: this crate
+// compiles to `wasm32-unknown-unknown` with no C toolchain, same as flate2/brotli.
+
+#[wasm_bindgen]
+pub fn compress(input: Uint8Array, level: Option<u32>) -> Result<Uint8Array, JsValue> {
+    let input_vec: Vec<u8> = input.to_vec();
+    let lvl = level.unwrap_or(3).clamp(1, 22) as i32;
+
+    let compressed = ruzstd::encoding::compress_to_vec(&input_vec, lvl);
+    Ok(Uint8Array::from(&compressed[..]))
+}
+
+#[wasm_bindgen]
+pub fn decompress(input: Uint8Array) -> Result<Uint8Array, JsValue> {
+    let input_vec: Vec<u8> = input.to_vec();
+
+    let mut decoder = ruzstd::decoding::StreamingDecoder::new(&input_vec[..]).map_err(to_js)?;
+    let mut decompressed = Vec::new();
+    decoder.read_to_end(&mut decompressed).map_err(to_js)?;
+
+    Ok(Uint8Array::from(&decompressed[..]))
+}
+
+fn to_js<E: std::fmt::Display>(e: E) -> JsValue {
+    JsValue::from_str(&format!("{e}"))
+}
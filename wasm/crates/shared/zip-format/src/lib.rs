@@ -0,0 +1,641 @@
+//! ZIP-format building blocks shared by the single-file (`compression/zip`) and
+//! multi-file (`archive/zip`) crates: ZIP64 extra fields, WinZip AE-2 (AES)
+//! encryption, and data-descriptor (streamed-entry) parsing. Kept as one module
+//! so the two crates can't drift out of sync on the same on-disk format.
+
+use wasm_bindgen::prelude::*;
+use flate2::{Decompress, FlushDecompress, Status};
+use aes::{Aes128, Aes192, Aes256};
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use pbkdf2::pbkdf2_hmac;
+use subtle::ConstantTimeEq;
+
+type Aes128CtrLe = ctr::Ctr128LE<Aes128>;
+type Aes192CtrLe = ctr::Ctr128LE<Aes192>;
+type Aes256CtrLe = ctr::Ctr128LE<Aes256>;
+
+pub const AE_EXTRA_ID: u16 = 0x9901;
+pub const AE_VENDOR_VERSION: u16 = 2; // AE-2: no separate CRC, integrity comes from the HMAC
+pub const AE_VENDOR_ID: &[u8; 2] = b"AE";
+pub const METHOD_AES: u16 = 99;
+
+pub const ZIP64_EXTRA_ID: u16 = 0x0001;
+pub const SENTINEL32: u32 = 0xFFFFFFFF;
+pub const SENTINEL16: u16 = 0xFFFF;
+
+pub fn to_js<E: std::fmt::Display>(e: E) -> JsValue {
+    JsValue::from_str(&format!("{e}"))
+}
+
+#[inline]
+pub fn le_u16(buf: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([buf[i], buf[i + 1]])
+}
+
+#[inline]
+pub fn le_u32(buf: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]])
+}
+
+#[inline]
+pub fn le_u64(buf: &[u8], i: usize) -> u64 {
+    u64::from_le_bytes(buf[i..i + 8].try_into().unwrap())
+}
+
+// ---- ZIP64 ----
+
+#[inline]
+pub fn sentinel32_or(value: u64, use_sentinel: bool) -> [u8; 4] {
+    if use_sentinel { SENTINEL32.to_le_bytes() } else { (value as u32).to_le_bytes() }
+}
+
+/// Local-header 0x0001 extra field: carries only the fields whose classic
+/// counterpart is the sentinel, in order (uncompressed size, then compressed
+/// size) — each field is present independently, not all-or-nothing.
+pub fn zip64_local_extra(uncomp_size: u64, comp_size: u64) -> Vec<u8> {
+    let mut data = Vec::new();
+    if uncomp_size >= SENTINEL32 as u64 {
+        data.extend_from_slice(&uncomp_size.to_le_bytes());
+    }
+    if comp_size >= SENTINEL32 as u64 {
+        data.extend_from_slice(&comp_size.to_le_bytes());
+    }
+    if data.is_empty() {
+        return Vec::new();
+    }
+    let mut extra = Vec::with_capacity(4 + data.len());
+    extra.extend_from_slice(&u16::to_le_bytes(ZIP64_EXTRA_ID));
+    extra.extend_from_slice(&u16::to_le_bytes(data.len() as u16));
+    extra.extend_from_slice(&data);
+    extra
+}
+
+/// Central-directory 0x0001 extra field: carries only the fields whose classic
+/// counterpart is the sentinel, in order (uncompressed size, compressed size, local-header offset).
+pub fn zip64_central_extra(uncomp_size: u64, comp_size: u64, rel_offset: u64) -> Vec<u8> {
+    let mut data = Vec::new();
+    if uncomp_size >= SENTINEL32 as u64 {
+        data.extend_from_slice(&uncomp_size.to_le_bytes());
+    }
+    if comp_size >= SENTINEL32 as u64 {
+        data.extend_from_slice(&comp_size.to_le_bytes());
+    }
+    if rel_offset >= SENTINEL32 as u64 {
+        data.extend_from_slice(&rel_offset.to_le_bytes());
+    }
+    if data.is_empty() {
+        return Vec::new();
+    }
+    let mut extra = Vec::with_capacity(4 + data.len());
+    extra.extend_from_slice(&u16::to_le_bytes(ZIP64_EXTRA_ID));
+    extra.extend_from_slice(&u16::to_le_bytes(data.len() as u16));
+    extra.extend_from_slice(&data);
+    extra
+}
+
+/// Whether a local header's extra field contains a ZIP64 (0x0001) record.
+pub fn has_zip64_extra(extra: &[u8]) -> bool {
+    let mut i = 0;
+    while i + 4 <= extra.len() {
+        let id = le_u16(extra, i);
+        let size = le_u16(extra, i + 2) as usize;
+        if id == ZIP64_EXTRA_ID {
+            return true;
+        }
+        i += 4 + size;
+    }
+    false
+}
+
+/// Resolve a central-directory entry's true `(uncompressed_size, compressed_size,
+/// local_header_offset)`, reading the ZIP64 0x0001 extra field for whichever classic
+/// 32-bit fields are the sentinel, independently, in the same order [`zip64_central_extra`]
+/// writes them (uncompressed size, compressed size, local-header offset).
+pub fn read_zip64_central_extra(
+    extra: &[u8],
+    uncomp_size_raw: u32,
+    comp_size_raw: u32,
+    rel_offset_raw: u32,
+) -> Result<(u64, u64, u64), JsValue> {
+    let need_uncomp = uncomp_size_raw == SENTINEL32;
+    let need_comp = comp_size_raw == SENTINEL32;
+    let need_offset = rel_offset_raw == SENTINEL32;
+    if !need_uncomp && !need_comp && !need_offset {
+        return Ok((uncomp_size_raw as u64, comp_size_raw as u64, rel_offset_raw as u64));
+    }
+
+    let mut i = 0;
+    while i + 4 <= extra.len() {
+        let id = le_u16(extra, i);
+        let size = le_u16(extra, i + 2) as usize;
+        let field_start = i + 4;
+        let field_end = field_start + size;
+        if id == ZIP64_EXTRA_ID && field_end <= extra.len() {
+            let mut p = field_start;
+            let mut take = || -> Result<u64, JsValue> {
+                if p + 8 > field_end {
+                    return Err(JsValue::from_str("Truncated ZIP64 extra field"));
+                }
+                let v = le_u64(extra, p);
+                p += 8;
+                Ok(v)
+            };
+            let uncomp_size = if need_uncomp { take()? } else { uncomp_size_raw as u64 };
+            let comp_size = if need_comp { take()? } else { comp_size_raw as u64 };
+            let rel_offset = if need_offset { take()? } else { rel_offset_raw as u64 };
+            return Ok((uncomp_size, comp_size, rel_offset));
+        }
+        i = field_end;
+    }
+    Err(JsValue::from_str("Missing ZIP64 extra field for oversized entry"))
+}
+
+/// Resolve a local header's true `(uncompressed_size, compressed_size)`, reading the
+/// ZIP64 0x0001 extra field for whichever of the two classic 32-bit fields is the
+/// sentinel. Each field is looked up independently since the extra field only ever
+/// carries the ones that actually overflowed.
+pub fn read_zip64_local_extra(extra: &[u8], uncomp_size_raw: u32, comp_size_raw: u32) -> Result<(u64, u64), JsValue> {
+    let need_uncomp = uncomp_size_raw == SENTINEL32;
+    let need_comp = comp_size_raw == SENTINEL32;
+    if !need_uncomp && !need_comp {
+        return Ok((uncomp_size_raw as u64, comp_size_raw as u64));
+    }
+
+    let mut i = 0;
+    while i + 4 <= extra.len() {
+        let id = le_u16(extra, i);
+        let size = le_u16(extra, i + 2) as usize;
+        let field_start = i + 4;
+        let field_end = field_start + size;
+        if id == ZIP64_EXTRA_ID && field_end <= extra.len() {
+            let mut p = field_start;
+            let uncomp_size = if need_uncomp {
+                if p + 8 > field_end {
+                    return Err(JsValue::from_str("Truncated ZIP64 extra field"));
+                }
+                let v = le_u64(extra, p);
+                p += 8;
+                v
+            } else {
+                uncomp_size_raw as u64
+            };
+            let comp_size = if need_comp {
+                if p + 8 > field_end {
+                    return Err(JsValue::from_str("Truncated ZIP64 extra field"));
+                }
+                le_u64(extra, p)
+            } else {
+                comp_size_raw as u64
+            };
+            return Ok((uncomp_size, comp_size));
+        }
+        i = field_end;
+    }
+    Err(JsValue::from_str("Missing ZIP64 extra field for oversized entry"))
+}
+
+/// Decompress raw-deflate data whose length isn't known up front, stopping as soon as
+/// the decoder reports end-of-stream. Returns `(decompressed_bytes, bytes_of_input_consumed)`.
+pub fn inflate_until_end(input: &[u8]) -> Result<(Vec<u8>, usize), JsValue> {
+    let mut decompress = Decompress::new(false);
+    let mut out = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let before_in = decompress.total_in() as usize;
+        if before_in >= input.len() {
+            return Err(JsValue::from_str("Truncated deflate stream in data-descriptor entry"));
+        }
+        let before_out = decompress.total_out();
+        let status = decompress.decompress(&input[before_in..], &mut buf, FlushDecompress::None).map_err(to_js)?;
+        let produced = (decompress.total_out() - before_out) as usize;
+        out.extend_from_slice(&buf[..produced]);
+        if status == Status::StreamEnd {
+            break;
+        }
+    }
+    Ok((out, decompress.total_in() as usize))
+}
+
+/// Scan forward for the next local/central-directory header or data-descriptor signature.
+pub fn scan_for_boundary(data: &[u8], start: usize) -> Option<usize> {
+    let mut i = start;
+    while i + 4 <= data.len() {
+        if &data[i..i + 4] == b"PK\x07\x08" || &data[i..i + 4] == b"PK\x03\x04" || &data[i..i + 4] == b"PK\x01\x02" {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Read a data-descriptor (streamed) entry whose local header carried zero sizes and CRC.
+/// Returns `(decompressed_bytes, crc32_from_descriptor, pos_after_descriptor)`.
+pub fn read_streamed_entry(data: &[u8], start: usize, method: u16, zip64: bool) -> Result<(Vec<u8>, u32, usize), JsValue> {
+    let (file_bytes, comp_size) = match method {
+        8 => inflate_until_end(&data[start..])?,
+        0 => {
+            let boundary = scan_for_boundary(data, start)
+                .ok_or_else(|| JsValue::from_str("Corrupt ZIP: could not locate end of stored data-descriptor entry"))?;
+            (data[start..boundary].to_vec(), boundary - start)
+        }
+        _ => return Err(JsValue::from_str("Unsupported compression method for data-descriptor entry")),
+    };
+
+    let mut desc_pos = start + comp_size;
+    if data.len() >= desc_pos + 4 && &data[desc_pos..desc_pos + 4] == b"PK\x07\x08" {
+        desc_pos += 4;
+    }
+
+    let field_width = if zip64 { 8 } else { 4 };
+    if desc_pos + 4 + field_width * 2 > data.len() {
+        return Err(JsValue::from_str("Corrupt ZIP: truncated data descriptor"));
+    }
+    let crc32 = le_u32(data, desc_pos);
+    let next_pos = desc_pos + 4 + field_width * 2;
+
+    Ok((file_bytes, crc32, next_pos))
+}
+
+/// Write the (classic, and ZIP64-prefixed when needed) End Of Central Directory records.
+pub fn write_eocd(out: &mut Vec<u8>, entry_count: u64, cd_size: u64, cd_start: u64) {
+    let needs_zip64 = entry_count >= SENTINEL16 as u64 || cd_start >= SENTINEL32 as u64 || cd_size >= SENTINEL32 as u64;
+
+    if needs_zip64 {
+        let eocd64_offset = out.len() as u64;
+
+        // ---- ZIP64 End Of Central Directory record (PK\x06\x06) ----
+        out.extend_from_slice(b"PK\x06\x06");
+        out.extend_from_slice(&u64::to_le_bytes(44)); // size of this record, minus the leading 12 bytes
+        out.extend_from_slice(&u16::to_le_bytes(45)); // version made by
+        out.extend_from_slice(&u16::to_le_bytes(45)); // version needed to extract
+        out.extend_from_slice(&u32::to_le_bytes(0));  // disk number
+        out.extend_from_slice(&u32::to_le_bytes(0));  // disk with cd
+        out.extend_from_slice(&u64::to_le_bytes(entry_count)); // entries this disk
+        out.extend_from_slice(&u64::to_le_bytes(entry_count)); // total entries
+        out.extend_from_slice(&u64::to_le_bytes(cd_size));     // cd size
+        out.extend_from_slice(&u64::to_le_bytes(cd_start));    // cd offset
+
+        // ---- ZIP64 End Of Central Directory locator (PK\x06\x07) ----
+        out.extend_from_slice(b"PK\x06\x07");
+        out.extend_from_slice(&u32::to_le_bytes(0));  // disk with zip64 eocd
+        out.extend_from_slice(&u64::to_le_bytes(eocd64_offset));
+        out.extend_from_slice(&u32::to_le_bytes(1));  // total number of disks
+    }
+
+    // ---- End of central directory (PK\x05\x06) ----
+    let entries16 = if entry_count >= SENTINEL16 as u64 { SENTINEL16 } else { entry_count as u16 };
+    out.extend_from_slice(b"PK\x05\x06");
+    out.extend_from_slice(&u16::to_le_bytes(0));       // disk no
+    out.extend_from_slice(&u16::to_le_bytes(0));       // disk w/ cd
+    out.extend_from_slice(&u16::to_le_bytes(entries16)); // entries this disk
+    out.extend_from_slice(&u16::to_le_bytes(entries16)); // total entries
+    out.extend_from_slice(&sentinel32_or(cd_size, cd_size >= SENTINEL32 as u64));
+    out.extend_from_slice(&sentinel32_or(cd_start, cd_start >= SENTINEL32 as u64));
+    out.extend_from_slice(&u16::to_le_bytes(0));       // comment len
+}
+
+/// Locate the central directory via the End Of Central Directory record (scanning
+/// backwards for `PK\x05\x06`, then following the ZIP64 locator/record if present),
+/// returning `(cd_offset, cd_size)`. Multi-entry archives should read the central
+/// directory to find each entry's local header rather than scanning forward for
+/// `PK\x03\x04` signatures from byte 0 — a scan can be fooled by caller-supplied bytes
+/// (e.g. a polyglot cover file) that happen to contain that 4-byte sequence.
+pub fn locate_central_directory(data: &[u8]) -> Result<(u64, u64), JsValue> {
+    const EOCD_LEN: usize = 22;
+    if data.len() < EOCD_LEN {
+        return Err(JsValue::from_str("Corrupt ZIP: too small to contain an end-of-central-directory record"));
+    }
+
+    let scan_floor = data.len().saturating_sub(EOCD_LEN + u16::MAX as usize);
+    let mut eocd_pos = None;
+    let mut i = data.len() - EOCD_LEN + 1;
+    while i > scan_floor {
+        i -= 1;
+        if &data[i..i + 4] == b"PK\x05\x06" {
+            eocd_pos = Some(i);
+            break;
+        }
+    }
+    let eocd_pos = eocd_pos.ok_or_else(|| JsValue::from_str("Corrupt ZIP: missing end-of-central-directory record"))?;
+
+    let entries16 = le_u16(data, eocd_pos + 10);
+    let cd_size32 = le_u32(data, eocd_pos + 12);
+    let cd_offset32 = le_u32(data, eocd_pos + 16);
+
+    let needs_zip64 = entries16 == SENTINEL16 || cd_size32 == SENTINEL32 || cd_offset32 == SENTINEL32;
+    if !needs_zip64 {
+        return Ok((cd_offset32 as u64, cd_size32 as u64));
+    }
+
+    // ZIP64 End Of Central Directory locator (PK\x06\x07), fixed 20 bytes, sits directly
+    // before the classic EOCD: sig(4) + disk(4) + zip64_eocd_offset(8) + total_disks(4).
+    if eocd_pos < 20 || &data[eocd_pos - 20..eocd_pos - 16] != b"PK\x06\x07" {
+        return Err(JsValue::from_str("Corrupt ZIP: missing ZIP64 end-of-central-directory locator"));
+    }
+    let zip64_eocd_offset = le_u64(data, eocd_pos - 20 + 8) as usize;
+    if zip64_eocd_offset + 56 > data.len() || &data[zip64_eocd_offset..zip64_eocd_offset + 4] != b"PK\x06\x06" {
+        return Err(JsValue::from_str("Corrupt ZIP: missing ZIP64 end-of-central-directory record"));
+    }
+    let cd_size = le_u64(data, zip64_eocd_offset + 40);
+    let cd_offset = le_u64(data, zip64_eocd_offset + 48);
+    Ok((cd_offset, cd_size))
+}
+
+// ---- WinZip AE-2 (AES) encryption ----
+
+/// The CRC-32 value to store in a local/central-directory header for `method`. AE-2
+/// entries (see [`AE_VENDOR_VERSION`]) carry no plaintext CRC — integrity comes from
+/// the trailing HMAC instead — so the header field must be written as 0.
+#[inline]
+pub fn header_crc32(method: u16, crc: u32) -> u32 {
+    if method == METHOD_AES { 0 } else { crc }
+}
+
+const VERSION_NEEDED_CLASSIC: u16 = 20;
+const VERSION_NEEDED_ZIP64: u16 = 45;
+/// Per APPNOTE: WinZip AE-x entries must advertise version-needed 5.1, so a strict
+/// reader knows decryption is required before it ever inspects the method-99 field.
+const VERSION_NEEDED_AES: u16 = 51;
+
+/// "Version needed to extract" for a local/central-directory header: the highest of
+/// the classic baseline, ZIP64 (if `has_zip64_extra`), and WinZip AE-x (if `method` is
+/// [`METHOD_AES`]) requirements.
+#[inline]
+pub fn version_needed(method: u16, has_zip64_extra: bool) -> u16 {
+    let mut v = VERSION_NEEDED_CLASSIC;
+    if has_zip64_extra {
+        v = v.max(VERSION_NEEDED_ZIP64);
+    }
+    if method == METHOD_AES {
+        v = v.max(VERSION_NEEDED_AES);
+    }
+    v
+}
+
+/// Salt length and AES-strength code for a given key size, per the WinZip AE spec.
+pub fn aes_params(bits: u16) -> Result<(usize, u8), JsValue> {
+    match bits {
+        128 => Ok((8, 1)),
+        192 => Ok((12, 2)),
+        256 => Ok((16, 3)),
+        _ => Err(JsValue::from_str("Unsupported AES strength (use 128, 192, or 256)")),
+    }
+}
+
+/// Key material derived from a password: the AES key, the HMAC-SHA1 authentication
+/// key, and the 2-byte password-verification value, per the WinZip AE key-derivation scheme.
+pub struct AesKeys {
+    pub aes_key: Vec<u8>,
+    pub hmac_key: Vec<u8>,
+    pub verify: [u8; 2],
+}
+
+pub fn derive_aes_keys(password: &str, salt: &[u8], key_len: usize) -> AesKeys {
+    let mut derived = vec![0u8; key_len * 2 + 2];
+    pbkdf2_hmac::<Sha1>(password.as_bytes(), salt, 1000, &mut derived);
+    let aes_key = derived[..key_len].to_vec();
+    let hmac_key = derived[key_len..key_len * 2].to_vec();
+    let mut verify = [0u8; 2];
+    verify.copy_from_slice(&derived[key_len * 2..]);
+    AesKeys { aes_key, hmac_key, verify }
+}
+
+/// Apply AES-CTR keystream in place, little-endian counter starting at 1 (one counter block per 16 bytes).
+pub fn aes_ctr_apply(key: &[u8], data: &mut [u8]) {
+    let mut iv = [0u8; 16];
+    iv[0] = 1;
+    match key.len() {
+        16 => Aes128CtrLe::new(key.into(), &iv.into()).apply_keystream(data),
+        24 => Aes192CtrLe::new(key.into(), &iv.into()).apply_keystream(data),
+        32 => Aes256CtrLe::new(key.into(), &iv.into()).apply_keystream(data),
+        _ => unreachable!("key length validated by aes_params"),
+    }
+}
+
+pub fn hmac_sha1_10(key: &[u8], data: &[u8]) -> [u8; 10] {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    let full = mac.finalize().into_bytes();
+    let mut out = [0u8; 10];
+    out.copy_from_slice(&full[..10]);
+    out
+}
+
+/// Encrypt `payload` (already deflated, if at all) under WinZip AE-2, returning
+/// `salt || password_verify || ciphertext || hmac_sha1[..10]`.
+pub fn encrypt_winzip_aes(payload: &[u8], password: &str, bits: u16) -> Result<Vec<u8>, JsValue> {
+    let (salt_len, _strength) = aes_params(bits)?;
+    let mut salt = vec![0u8; salt_len];
+    getrandom::getrandom(&mut salt).map_err(to_js)?;
+
+    let keys = derive_aes_keys(password, &salt, (bits / 8) as usize);
+    let mut ciphertext = payload.to_vec();
+    aes_ctr_apply(&keys.aes_key, &mut ciphertext);
+    let mac = hmac_sha1_10(&keys.hmac_key, &ciphertext);
+
+    let mut out = Vec::with_capacity(salt_len + 2 + ciphertext.len() + 10);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&keys.verify);
+    out.extend_from_slice(&ciphertext);
+    out.extend_from_slice(&mac);
+    Ok(out)
+}
+
+/// Reverse of [`encrypt_winzip_aes`]: verify the password-verification value and the
+/// trailing HMAC before decrypting, returning the original (still possibly deflated) payload.
+pub fn decrypt_winzip_aes(blob: &[u8], password: &str, bits: u16) -> Result<Vec<u8>, JsValue> {
+    let (salt_len, _strength) = aes_params(bits)?;
+    if blob.len() < salt_len + 2 + 10 {
+        return Err(JsValue::from_str("Corrupt AES-encrypted entry: too short"));
+    }
+    let salt = &blob[..salt_len];
+    let verify = &blob[salt_len..salt_len + 2];
+    let ciphertext = &blob[salt_len + 2..blob.len() - 10];
+    let stored_mac = &blob[blob.len() - 10..];
+
+    let keys = derive_aes_keys(password, salt, (bits / 8) as usize);
+    // Constant-time: these guard a password/authentication check, and a variable-time
+    // (short-circuiting) byte comparison here would leak how many leading bytes matched.
+    if !bool::from(keys.verify.ct_eq(verify)) {
+        return Err(JsValue::from_str("Wrong password"));
+    }
+    let computed_mac = hmac_sha1_10(&keys.hmac_key, ciphertext);
+    if !bool::from(computed_mac.ct_eq(stored_mac)) {
+        return Err(JsValue::from_str("Corrupt AES-encrypted entry: HMAC mismatch"));
+    }
+
+    let mut plain = ciphertext.to_vec();
+    aes_ctr_apply(&keys.aes_key, &mut plain);
+    Ok(plain)
+}
+
+/// Build the 0x9901 "AE-x" extra field recording the real compression method and AES strength.
+pub fn ae_extra_field(aes_bits: u16, real_method: u16) -> Result<Vec<u8>, JsValue> {
+    let (_salt_len, strength) = aes_params(aes_bits)?;
+    let mut extra = Vec::with_capacity(11);
+    extra.extend_from_slice(&u16::to_le_bytes(AE_EXTRA_ID));
+    extra.extend_from_slice(&u16::to_le_bytes(7)); // data size
+    extra.extend_from_slice(&u16::to_le_bytes(AE_VENDOR_VERSION));
+    extra.extend_from_slice(AE_VENDOR_ID);
+    extra.push(strength);
+    extra.extend_from_slice(&u16::to_le_bytes(real_method));
+    Ok(extra)
+}
+
+/// Parse the 0x9901 "AE-x" extra field, returning `(real_method, aes_bits)`.
+pub fn read_ae_extra_field(extra: &[u8]) -> Result<(u16, u16), JsValue> {
+    let mut i = 0;
+    while i + 4 <= extra.len() {
+        let id = le_u16(extra, i);
+        let size = le_u16(extra, i + 2) as usize;
+        let field_start = i + 4;
+        if id == AE_EXTRA_ID && field_start + size <= extra.len() && size >= 7 {
+            let strength = extra[field_start + 4];
+            let real_method = le_u16(extra, field_start + 5);
+            let aes_bits = match strength {
+                1 => 128,
+                2 => 192,
+                3 => 256,
+                _ => return Err(JsValue::from_str("Unknown AES strength in AE-x extra field")),
+            };
+            return Ok((real_method, aes_bits));
+        }
+        i = field_start + size;
+    }
+    Err(JsValue::from_str("Missing AE-x extra field for AES-encrypted entry"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ae2_round_trip() {
+        let payload = b"the quick brown fox jumps over the lazy dog".to_vec();
+        let blob = encrypt_winzip_aes(&payload, "hunter2", 256).unwrap();
+        let plain = decrypt_winzip_aes(&blob, "hunter2", 256).unwrap();
+        assert_eq!(plain, payload);
+    }
+
+    #[test]
+    fn ae2_wrong_password_is_rejected() {
+        let payload = b"secret data".to_vec();
+        let blob = encrypt_winzip_aes(&payload, "correct horse", 256).unwrap();
+        assert!(decrypt_winzip_aes(&blob, "incorrect horse", 256).is_err());
+    }
+
+    #[test]
+    fn ae2_tampered_ciphertext_fails_hmac_check() {
+        let payload = b"do not modify me".to_vec();
+        let mut blob = encrypt_winzip_aes(&payload, "hunter2", 256).unwrap();
+        let flip = blob.len() - 11; // inside the ciphertext, just before the trailing HMAC
+        blob[flip] ^= 0xFF;
+        assert!(decrypt_winzip_aes(&blob, "hunter2", 256).is_err());
+    }
+
+    #[test]
+    fn ae2_truncated_blob_is_rejected() {
+        let payload = b"short".to_vec();
+        let blob = encrypt_winzip_aes(&payload, "hunter2", 128).unwrap();
+        assert!(decrypt_winzip_aes(&blob[..blob.len() - 1], "hunter2", 128).is_err());
+    }
+
+    #[test]
+    fn header_crc32_is_zero_only_for_ae2_method() {
+        assert_eq!(header_crc32(METHOD_AES, 0xDEAD_BEEF), 0);
+        assert_eq!(header_crc32(8, 0xDEAD_BEEF), 0xDEAD_BEEF);
+    }
+
+    #[test]
+    fn zip64_local_extra_omitted_when_both_sizes_fit() {
+        assert!(zip64_local_extra(100, 50).is_empty());
+    }
+
+    #[test]
+    fn zip64_local_extra_one_field_when_only_one_size_overflows() {
+        // A highly compressible file: uncompressed size overflows 4 GiB, compressed doesn't.
+        let huge_uncomp = SENTINEL32 as u64 + 1;
+        let extra = zip64_local_extra(huge_uncomp, 1000);
+        assert_eq!(extra.len(), 4 + 8); // one 8-byte field, not the all-or-nothing 16
+
+        let (uncomp, comp) = read_zip64_local_extra(&extra, SENTINEL32, 1000).unwrap();
+        assert_eq!(uncomp, huge_uncomp);
+        assert_eq!(comp, 1000);
+    }
+
+    #[test]
+    fn zip64_local_extra_both_fields_when_both_sizes_overflow() {
+        let huge_uncomp = SENTINEL32 as u64 + 1;
+        let huge_comp = SENTINEL32 as u64 + 2;
+        let extra = zip64_local_extra(huge_uncomp, huge_comp);
+        assert_eq!(extra.len(), 4 + 16);
+
+        let (uncomp, comp) = read_zip64_local_extra(&extra, SENTINEL32, SENTINEL32).unwrap();
+        assert_eq!(uncomp, huge_uncomp);
+        assert_eq!(comp, huge_comp);
+    }
+
+    #[test]
+    fn zip64_local_extra_not_triggered_one_byte_under_the_sentinel() {
+        let just_under = SENTINEL32 as u64 - 1;
+        assert!(zip64_local_extra(just_under, just_under).is_empty());
+        let (uncomp, comp) = read_zip64_local_extra(&[], just_under as u32, just_under as u32).unwrap();
+        assert_eq!((uncomp, comp), (just_under, just_under));
+    }
+
+    #[test]
+    fn zip64_local_extra_triggered_at_exactly_the_sentinel() {
+        // 4 GiB exactly: the classic field would itself read as the sentinel, so it must
+        // be promoted to the ZIP64 extra field rather than written as a (truncated) literal.
+        let exactly_4gib = SENTINEL32 as u64;
+        let extra = zip64_local_extra(exactly_4gib, exactly_4gib);
+        assert_eq!(extra.len(), 4 + 16);
+        let (uncomp, comp) = read_zip64_local_extra(&extra, SENTINEL32, SENTINEL32).unwrap();
+        assert_eq!((uncomp, comp), (exactly_4gib, exactly_4gib));
+    }
+
+    #[test]
+    fn read_zip64_central_extra_resolves_each_field_independently() {
+        let extra = zip64_central_extra(SENTINEL32 as u64 + 5, 10, SENTINEL32 as u64 + 7);
+        let (uncomp, comp, offset) =
+            read_zip64_central_extra(&extra, SENTINEL32, 10, SENTINEL32).unwrap();
+        assert_eq!(uncomp, SENTINEL32 as u64 + 5);
+        assert_eq!(comp, 10);
+        assert_eq!(offset, SENTINEL32 as u64 + 7);
+    }
+
+    #[test]
+    fn read_streamed_entry_stored_method_extracts_descriptor_fields() {
+        let payload = b"stored data, no compression".to_vec();
+        let mut data = payload.clone();
+        data.extend_from_slice(b"PK\x07\x08");
+        data.extend_from_slice(&0xDEAD_BEEFu32.to_le_bytes()); // crc32, from the descriptor
+        data.extend_from_slice(&(payload.len() as u32).to_le_bytes()); // comp size
+        data.extend_from_slice(&(payload.len() as u32).to_le_bytes()); // uncomp size
+
+        let (out, crc, next_pos) = read_streamed_entry(&data, 0, 0, false).unwrap();
+        assert_eq!(out, payload);
+        // read_streamed_entry only extracts the descriptor's CRC; verifying it against
+        // the actual decompressed bytes is the caller's job (and where a mismatch error
+        // surfaces for tampered/truncated data-descriptor entries).
+        assert_eq!(crc, 0xDEAD_BEEF);
+        assert_eq!(next_pos, data.len());
+    }
+
+    #[test]
+    fn read_streamed_entry_rejects_truncated_descriptor() {
+        let payload = b"short".to_vec();
+        let mut data = payload.clone();
+        data.extend_from_slice(b"PK\x07\x08");
+        data.extend_from_slice(&0u32.to_le_bytes()); // crc only; missing both size fields
+        assert!(read_streamed_entry(&data, 0, 0, false).is_err());
+    }
+
+    #[test]
+    fn read_streamed_entry_rejects_stored_data_with_no_terminating_signature() {
+        let data = b"just some bytes with no terminating PK signature anywhere".to_vec();
+        assert!(read_streamed_entry(&data, 0, 0, false).is_err());
+    }
+}